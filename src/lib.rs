@@ -34,14 +34,47 @@
 //! ```
 //!
 //! Note: Because the hello world program example doesn't use the `,` input command, we can use
-//! `io::empty()` as the input. However, if we provided `io::empty()` for a program which did use
-//! `,`, `execute_brainfuck()` would loop indefinitely waiting for input.
+//! `io::empty()` as the input. If we provided `io::empty()` for a program which did use `,`,
+//! `execute_brainfuck()` would not hang -- it resolves the `,` per `ExecutionConfig::eof_behavior`
+//! (the default, `EofBehavior::Unchanged`, just leaves the current cell's value as-is) and keeps
+//! going. See `Interpreter::signal_eof` and `EofBehavior` for the other options.
+//!
+//! Optimizing
+//! ==========
+//!
+//! Running a `Vec<Instruction>` straight out of `parse_instructions` works fine, but it's mostly
+//! a direct transcription of the source: each `+`/`-`/`<`/`>` is a separate instruction, and
+//! idiomatic loops like `[-]` run one cell decrement at a time. `optimize_instructions` coalesces
+//! runs of `+`/`-`/`<`/`>` into single `Add`/`Move` steps and folds common loop idioms (clearing
+//! a cell, multiplying/copying between cells) into `SetZero`/`MulLoop`, without changing the
+//! program's observable behavior -- as long as `ExecutionConfig::{cell_overflow,pointer_overflow}`
+//! are left at their default `Wrap` settings. (Folding is skipped entirely under `Error` settings,
+//! since collapsing a run can erase a boundary violation the unfolded program would have legitimately hit partway through.)
+//!
+//! Resumable execution
+//! ====================
+//!
+//! `execute_brainfuck`/`execute_brainfuck_with_config` block on `io::Read`/`io::Write`, which
+//! doesn't fit every caller -- for example, one driving the program from an interactive event
+//! loop one keystroke at a time. `Interpreter` is the lower-level, resumable building block they're
+//! implemented on top of: `Interpreter::advance_until_io` runs until the next `.`, `,`, or halt,
+//! returning an `IoEvent` rather than reading or writing directly, so the caller decides how and
+//! when to supply input.
+//!
+//! `Interpreter::with_config` accepts an `ExecutionConfig` to customize tape size and the cell
+//! overflow/pointer overflow/EOF semantics described above; `Interpreter::with_source_positions`
+//! attaches the source offsets from `parse_instructions_with_positions` (optionally carried
+//! through optimization via `optimize_instructions_with_positions`) so that a `RuntimeError`
+//! reports where in the source the failing instruction came from.
 
+use std::collections::VecDeque;
 use std::fmt;
 use std::iter;
 use std::io;
 
 const MEMORY_SIZE: usize = 32768usize;
+// Number of cells captured on either side of the memory pointer in a `RuntimeError`'s snapshot.
+const RUNTIME_ERROR_SNAPSHOT_RADIUS: usize = 16usize;
 
 #[derive(Debug)]
 pub enum Error {
@@ -49,6 +82,12 @@ pub enum Error {
     UnbalancedRightBracket,
     /// The input ended before right brackets were found to match all left brackets.
     UnbalancedLeftBracket,
+    /// A cell's value over/underflowed and `ExecutionConfig::cell_overflow` was set to
+    /// `CellOverflow::Error`.
+    CellOverflow,
+    /// The memory pointer moved out of bounds and `ExecutionConfig::pointer_overflow` was set to
+    /// `PointerOverflow::Error`.
+    PointerOutOfBounds,
 }
 
 #[derive(Debug)]
@@ -57,6 +96,8 @@ pub enum ExecutionTerminationCondition {
     MaximumIterationsReached,
     /// The program finished executing all instructions
     AllInstructionsFinished,
+    /// Execution stopped early because of a runtime error.
+    Error(RuntimeError),
 }
 
 impl fmt::Display for Error {
@@ -68,6 +109,36 @@ impl fmt::Display for Error {
             &Error::UnbalancedLeftBracket => {
                 write!(formatter, "Unbalanced `[`. Expected matching `]`, found end of file.")
             },
+            &Error::CellOverflow => {
+                write!(formatter, "A cell's value overflowed or underflowed.")
+            },
+            &Error::PointerOutOfBounds => {
+                write!(formatter, "The memory pointer moved out of bounds.")
+            },
+        }
+    }
+}
+
+/// A runtime error, carrying enough context to point a debugger at the offending instruction.
+#[derive(Debug)]
+pub struct RuntimeError {
+    /// What went wrong.
+    pub kind: Error,
+    /// The source offset of the instruction that caused the error, if known. Only available when
+    /// the program was parsed with `parse_instructions_with_positions` and the resulting
+    /// `source_positions` were given to the `Interpreter` via `with_source_positions`.
+    pub source_pos: Option<usize>,
+    /// A window of the tape centered on the memory pointer at the time of the error.
+    pub memory: Vec<u8>,
+    /// The index into `memory` of the memory pointer at the time of the error.
+    pub pointer: usize,
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self.source_pos {
+            Some(pos) => write!(formatter, "{} (at source position {})", self.kind, pos),
+            None => write!(formatter, "{}", self.kind),
         }
     }
 }
@@ -81,10 +152,79 @@ impl fmt::Display for ExecutionTerminationCondition {
             &ExecutionTerminationCondition::AllInstructionsFinished => {
                 write!(formatter, "Finished normally.")
             },
+            &ExecutionTerminationCondition::Error(ref err) => {
+                write!(formatter, "Execution failed: {}", err)
+            },
+        }
+    }
+}
+
+/// Controls the cell and pointer semantics used while executing a program, for behaviors real
+/// brainfuck implementations disagree on.
+#[derive(Debug, Clone, Copy)]
+pub struct ExecutionConfig {
+    /// Number of cells on the tape. Defaults to the classic `32768`.
+    pub tape_size: usize,
+    /// What happens when `+`/`-` (or a coalesced `Add`) pushes a cell past `0`/`255`.
+    pub cell_overflow: CellOverflow,
+    /// What happens when `<`/`>` (or a coalesced `Move`) pushes the pointer past either end of
+    /// the tape.
+    pub pointer_overflow: PointerOverflow,
+    /// What a `,` does when the input stream has reached its end.
+    pub eof_behavior: EofBehavior,
+}
+
+impl Default for ExecutionConfig {
+    /// The classic 32768-cell wrapping tape, matching `execute_brainfuck`'s long-standing
+    /// behavior.
+    fn default() -> ExecutionConfig {
+        ExecutionConfig {
+            tape_size: MEMORY_SIZE,
+            cell_overflow: CellOverflow::Wrap,
+            pointer_overflow: PointerOverflow::Wrap,
+            eof_behavior: EofBehavior::Unchanged,
         }
     }
 }
 
+#[derive(Debug, Clone, Copy)]
+pub enum CellOverflow {
+    /// Wrap the value around, e.g. `255 + 1 == 0`.
+    Wrap,
+    /// Report `Error::CellOverflow` instead of wrapping.
+    Error,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum PointerOverflow {
+    /// Wrap the pointer back onto the tape, e.g. moving left from cell `0` goes to the last cell.
+    Wrap,
+    /// Report `Error::PointerOutOfBounds` instead of wrapping.
+    Error,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum EofBehavior {
+    /// Leave the current cell's value unchanged.
+    Unchanged,
+    /// Set the current cell to zero.
+    Zero,
+    /// Set the current cell to 255.
+    Max,
+}
+
+/// An event returned by `Interpreter::advance_until_io`, describing why execution paused.
+#[derive(Debug)]
+pub enum IoEvent {
+    /// The program executed a `.`; here's the byte it wrote.
+    Output(u8),
+    /// The program executed a `,`, but no input is available. Call `Interpreter::add_input` and
+    /// then `advance_until_io` again to continue; the `,` hasn't been consumed yet.
+    NeedInput,
+    /// The program stopped running and won't produce any more `IoEvent`s.
+    Halted(ExecutionTerminationCondition),
+}
+
 #[derive(Debug)]
 pub enum Instruction {
     /// Increment the memory pointer by one
@@ -107,18 +247,59 @@ pub enum Instruction {
     /// If the memory value at the memory pointer is non-zero, set the next instruction to the
     /// contained value.
     JumpToRight(usize),
+    /// Add the contained value to the memory value at the memory pointer.
+    ///
+    /// This replaces a run of consecutive `Increment`/`Decrement` instructions, produced by
+    /// `optimize_instructions`.
+    Add(i8),
+    /// Move the memory pointer by the contained offset.
+    ///
+    /// This replaces a run of consecutive `MoveRight`/`MoveLeft` instructions, produced by
+    /// `optimize_instructions`.
+    Move(isize),
+    /// Set the memory value at the memory pointer to zero.
+    ///
+    /// This replaces a "clear loop" (`[-]` or `[+]`), produced by `optimize_instructions`.
+    SetZero,
+    /// Multiply the current memory value by each contained factor and add the result to the
+    /// memory value at the corresponding offset, then zero the current memory value.
+    ///
+    /// This replaces a balanced "multiply/copy loop" whose body only moves the pointer and adds
+    /// to cells, returning to its start with a net delta of `-1` on the loop cell. Applied as a
+    /// single step when the current memory value is nonzero, and skipped when it's zero.
+    /// Produced by `optimize_instructions`.
+    MulLoop { offsets: Vec<(isize, i8)> },
+}
+
+/// The result of `parse_instructions_with_positions`: a parsed program plus the source offset
+/// each instruction came from.
+#[derive(Debug)]
+pub struct ParsedProgram {
+    pub instructions: Vec<Instruction>,
+    /// The offset (the index of the source character, counting from 0) `instructions[i]` was
+    /// parsed from, for every `i`. Parallel to and the same length as `instructions`.
+    pub source_positions: Vec<usize>,
 }
 
 pub fn parse_instructions<T>(input: T) -> Result<Vec<Instruction>, Error>
         where T: iter::Iterator<Item=char> {
+    parse_instructions_with_positions(input).map(|program| program.instructions)
+}
+
+/// Like `parse_instructions`, but also records the source offset each instruction was parsed
+/// from, so that runtime errors can point back at the offending character. See `ParsedProgram`.
+pub fn parse_instructions_with_positions<T>(input: T) -> Result<ParsedProgram, Error>
+        where T: iter::Iterator<Item=char> {
     // Vec of opening jumps waiting for a closing jump to find
     // each u16 is a position in the instructions vec.
     let mut waiting_opening_jumps = Vec::new();
     // Output vec of instructions
     let mut instructions = Vec::new();
+    // Source offset each entry in `instructions` was parsed from, parallel to `instructions`.
+    let mut source_positions = Vec::new();
 
     // Main loop to parse
-    for c in input {
+    for (position, c) in input.enumerate() {
         // Match on the next character
         let instruction = match c {
             '>' => Instruction::MoveRight,
@@ -157,6 +338,7 @@ pub fn parse_instructions<T>(input: T) -> Result<Vec<Instruction>, Error>
         };
 
         instructions.push(instruction);
+        source_positions.push(position);
     }
 
     // Check to make sure there are no more opening left brackets which didn't find a matching
@@ -167,71 +349,835 @@ pub fn parse_instructions<T>(input: T) -> Result<Vec<Instruction>, Error>
     }
 
     // Return the instructions generated!
-    return Ok(instructions);
+    return Ok(ParsedProgram { instructions: instructions, source_positions: source_positions });
 }
 
-pub fn execute_brainfuck<O, I>(instructions: Vec<Instruction>, mut output: O, mut input: I,
-        maximum_iterations: u64) -> io::Result<ExecutionTerminationCondition>
-        where O: io::Write, I: io::Read {
+/// Compact a freshly-parsed instruction vector by folding runs of repeated operators and common
+/// loop idioms into single instructions.
+///
+/// Consecutive `Increment`/`Decrement` instructions are coalesced into a single `Add` carrying
+/// their net delta, and consecutive `MoveRight`/`MoveLeft` instructions are coalesced into a
+/// single `Move` carrying their net offset. Clear loops (`[-]`/`[+]`) are then folded into
+/// `SetZero`, and balanced multiply/copy loops are folded into `MulLoop`. `JumpToLeft`/
+/// `JumpToRight` targets are recomputed to point at the post-folding positions, using the same
+/// push/backpatch approach `parse_instructions` uses while parsing brackets.
+///
+/// This is purely an optimization: running the result through `execute_brainfuck` produces the
+/// same observable behavior as running the original vector, just in fewer iterations against the
+/// `maximum_iterations` budget.
+///
+/// Folding a run collapses its intermediate steps, so it only preserves behavior when those
+/// intermediate steps can't be individually observed. In `ExecutionConfig::default()` (wrapping
+/// cells and pointer) that's always true. But with `CellOverflow::Error` or
+/// `PointerOverflow::Error`, a run like `<<<>>>` or `+++---` can legitimately cross a boundary
+/// partway through and then come back, and folding it into a net-zero `Move`/`Add` would silently
+/// erase that boundary violation instead of reporting it. So folding is only performed when
+/// `config` uses `CellOverflow::Wrap` and `PointerOverflow::Wrap`; otherwise `instructions` is
+/// returned unchanged, since running it unfolded is the only way to preserve the error.
+pub fn optimize_instructions(instructions: Vec<Instruction>, config: &ExecutionConfig) -> Vec<Instruction> {
+    // `source_positions` is only needed to keep a `ParsedProgram` aligned through folding; a
+    // throwaway one (of the right length) lets `optimize_instructions_with_positions` do the
+    // work without duplicating it here.
+    let throwaway_positions = (0..instructions.len()).collect();
+    let program = ParsedProgram { instructions: instructions, source_positions: throwaway_positions };
+    optimize_instructions_with_positions(program, config).instructions
+}
 
-    // Program memory, max size is 2^15
-    let mut memory = [0u8; MEMORY_SIZE];
-    // Current position in memory
-    let mut memory_position = 0usize;
-    // Next instruction to run
-    let mut next_instruction = 0usize;
-    // Buffer used for reading input
-    let mut read_buf = [0u8; 1];
+/// Like `optimize_instructions`, but also remaps `source_positions` so they stay aligned with
+/// the (possibly folded) `instructions`, 1:1 just like `parse_instructions_with_positions`
+/// produces. Each folded instruction is attributed the source position of the first original
+/// instruction that contributed to it.
+///
+/// This is the function to use alongside `Interpreter::with_source_positions`: since
+/// `optimize_instructions` virtually always changes the instruction count, calling
+/// `with_source_positions` with the *original*, unfolded positions would fail its length check
+/// and panic.
+pub fn optimize_instructions_with_positions(program: ParsedProgram, config: &ExecutionConfig) -> ParsedProgram {
+    if folding_preserves_errors(config) {
+        let (instructions, source_positions) = coalesce_runs(program.instructions, program.source_positions);
+        let (instructions, source_positions) = fold_loops(instructions, source_positions);
+        ParsedProgram { instructions: instructions, source_positions: source_positions }
+    } else {
+        program
+    }
+}
+
+/// Whether `optimize_instructions` can safely fold runs without erasing a boundary violation
+/// `config` would otherwise report.
+fn folding_preserves_errors(config: &ExecutionConfig) -> bool {
+    match (config.cell_overflow, config.pointer_overflow) {
+        (CellOverflow::Wrap, PointerOverflow::Wrap) => true,
+        _ => false,
+    }
+}
 
-    // u32::MAX as a limit to the number of iterations to run for a single program.
-    for _ in 0..maximum_iterations {
-        if next_instruction >= instructions.len() {
-            // We've reached the end of the instructions
-            return Ok(ExecutionTerminationCondition::AllInstructionsFinished);
+/// Coalesce runs of `Increment`/`Decrement` into `Add`, and runs of `MoveRight`/`MoveLeft` into
+/// `Move`. `source_positions` must be the same length as `instructions`; the returned positions
+/// are aligned 1:1 with the returned instructions, each folded instruction keeping the position
+/// of the first original instruction that contributed to it.
+fn coalesce_runs(instructions: Vec<Instruction>, source_positions: Vec<usize>) -> (Vec<Instruction>, Vec<usize>) {
+    let mut optimized = Vec::with_capacity(instructions.len());
+    let mut optimized_positions = Vec::with_capacity(instructions.len());
+    // Vec of opening jumps waiting for a closing jump to find, same as `parse_instructions`, but
+    // storing positions in the post-coalesced `optimized` vec rather than the input vec.
+    let mut waiting_opening_jumps = Vec::new();
+
+    let mut i = 0usize;
+    while i < instructions.len() {
+        match &instructions[i] {
+            &Instruction::Increment | &Instruction::Decrement => {
+                let run_start = i;
+                let mut delta = 0i32;
+                while i < instructions.len() {
+                    match &instructions[i] {
+                        &Instruction::Increment => delta += 1,
+                        &Instruction::Decrement => delta -= 1,
+                        _ => break,
+                    }
+                    i += 1;
+                }
+                optimized.push(Instruction::Add(delta as i8));
+                optimized_positions.push(source_positions[run_start]);
+            },
+            &Instruction::MoveRight | &Instruction::MoveLeft => {
+                let run_start = i;
+                let mut delta = 0isize;
+                while i < instructions.len() {
+                    match &instructions[i] {
+                        &Instruction::MoveRight => delta += 1,
+                        &Instruction::MoveLeft => delta -= 1,
+                        _ => break,
+                    }
+                    i += 1;
+                }
+                optimized.push(Instruction::Move(delta));
+                optimized_positions.push(source_positions[run_start]);
+            },
+            &Instruction::JumpToLeft(_) => {
+                waiting_opening_jumps.push(optimized.len());
+                // This is a placeholder, replaced below once the matching `JumpToRight` is found.
+                optimized.push(Instruction::JumpToLeft(0usize));
+                optimized_positions.push(source_positions[i]);
+                i += 1;
+            },
+            &Instruction::JumpToRight(_) => {
+                // `instructions` came from `parse_instructions`, so brackets are already
+                // guaranteed to be balanced.
+                let left_jump = waiting_opening_jumps.pop()
+                    .expect("unbalanced brackets in an already-parsed instruction vector");
+                optimized[left_jump] = Instruction::JumpToLeft(optimized.len());
+                optimized.push(Instruction::JumpToRight(left_jump));
+                optimized_positions.push(source_positions[i]);
+                i += 1;
+            },
+            // `Output`/`Input` pass through unchanged, and the rest are left alone if this is
+            // called on an already-optimized vector. Matched exhaustively (no wildcard) so a
+            // future new `Instruction` variant fails to compile here instead of panicking.
+            &Instruction::Output => { optimized.push(Instruction::Output); optimized_positions.push(source_positions[i]); i += 1; },
+            &Instruction::Input => { optimized.push(Instruction::Input); optimized_positions.push(source_positions[i]); i += 1; },
+            &Instruction::Add(delta) => { optimized.push(Instruction::Add(delta)); optimized_positions.push(source_positions[i]); i += 1; },
+            &Instruction::Move(delta) => { optimized.push(Instruction::Move(delta)); optimized_positions.push(source_positions[i]); i += 1; },
+            &Instruction::SetZero => { optimized.push(Instruction::SetZero); optimized_positions.push(source_positions[i]); i += 1; },
+            &Instruction::MulLoop { ref offsets } => {
+                optimized.push(Instruction::MulLoop { offsets: offsets.clone() });
+                optimized_positions.push(source_positions[i]);
+                i += 1;
+            },
         }
-        match instructions[next_instruction] {
+    }
+
+    (optimized, optimized_positions)
+}
+
+/// Fold clear loops (`[-]`/`[+]`) into `SetZero`, and balanced multiply/copy loops into
+/// `MulLoop`. Loops that don't match either idiom are left untouched. `source_positions` must be
+/// the same length as `instructions`; the returned positions are aligned 1:1 with the returned
+/// instructions, a folded loop keeping the position of its `JumpToLeft` (its `[`).
+///
+/// Expects `instructions` to already have consistent `JumpToLeft`/`JumpToRight` targets (e.g.
+/// straight from `parse_instructions` or `coalesce_runs`).
+fn fold_loops(instructions: Vec<Instruction>, source_positions: Vec<usize>) -> (Vec<Instruction>, Vec<usize>) {
+    let mut folded = Vec::with_capacity(instructions.len());
+    let mut folded_positions = Vec::with_capacity(instructions.len());
+    // Positions in `folded` of `JumpToLeft`s whose loop wasn't folded away, waiting for their
+    // matching `JumpToRight` so we can backpatch the target, same as `coalesce_runs`.
+    let mut waiting_opening_jumps = Vec::new();
+
+    let mut i = 0usize;
+    while i < instructions.len() {
+        match instructions[i] {
+            Instruction::JumpToLeft(target) => {
+                let body = &instructions[i + 1..target];
+                if is_clear_loop(body) {
+                    folded.push(Instruction::SetZero);
+                    folded_positions.push(source_positions[i]);
+                    i = target + 1;
+                } else if let Some(offsets) = detect_mul_loop(body) {
+                    folded.push(Instruction::MulLoop { offsets: offsets });
+                    folded_positions.push(source_positions[i]);
+                    i = target + 1;
+                } else {
+                    waiting_opening_jumps.push(folded.len());
+                    // This is a placeholder, replaced below once the matching `JumpToRight` is
+                    // found.
+                    folded.push(Instruction::JumpToLeft(0usize));
+                    folded_positions.push(source_positions[i]);
+                    i += 1;
+                }
+            },
+            Instruction::JumpToRight(_) => {
+                let left_jump = waiting_opening_jumps.pop()
+                    .expect("unbalanced brackets in an already-parsed instruction vector");
+                folded[left_jump] = Instruction::JumpToLeft(folded.len());
+                folded.push(Instruction::JumpToRight(left_jump));
+                folded_positions.push(source_positions[i]);
+                i += 1;
+            },
+            Instruction::Add(delta) => {
+                folded.push(Instruction::Add(delta));
+                folded_positions.push(source_positions[i]);
+                i += 1;
+            },
+            Instruction::Move(delta) => {
+                folded.push(Instruction::Move(delta));
+                folded_positions.push(source_positions[i]);
+                i += 1;
+            },
+            Instruction::Output => {
+                folded.push(Instruction::Output);
+                folded_positions.push(source_positions[i]);
+                i += 1;
+            },
+            Instruction::Input => {
+                folded.push(Instruction::Input);
+                folded_positions.push(source_positions[i]);
+                i += 1;
+            },
+            Instruction::Increment => {
+                folded.push(Instruction::Increment);
+                folded_positions.push(source_positions[i]);
+                i += 1;
+            },
+            Instruction::Decrement => {
+                folded.push(Instruction::Decrement);
+                folded_positions.push(source_positions[i]);
+                i += 1;
+            },
             Instruction::MoveRight => {
-                // Increment the position by one, and make sure it still fits into memory_size
-                memory_position += 1;
-                memory_position %= MEMORY_SIZE;
+                folded.push(Instruction::MoveRight);
+                folded_positions.push(source_positions[i]);
+                i += 1;
             },
             Instruction::MoveLeft => {
-                // Decrement the position by one, and make sure it still fits into memory_size
-                memory_position -= 1;
-                memory_position %= MEMORY_SIZE;
-            },
-            // Increment the memory value at the current position
-            Instruction::Increment => memory[memory_position] += 1,
-            // Decrement the memory value at the current position
-            Instruction::Decrement => memory[memory_position] -= 1,
-            // Writ the memory value at the current position to the given output
-            Instruction::Output => try!(write!(&mut output, "{}", &(memory[memory_position] as char))),
-            Instruction::Input => {
-                // TODO: More efficient implementation of this perhaps?
-                loop {
-                    if try!(input.read(&mut read_buf)) >= 1 {
-                        // If we've read at least 1 character, break.
-                        break;
-                    }
+                folded.push(Instruction::MoveLeft);
+                folded_positions.push(source_positions[i]);
+                i += 1;
+            },
+            Instruction::SetZero => {
+                folded.push(Instruction::SetZero);
+                folded_positions.push(source_positions[i]);
+                i += 1;
+            },
+            Instruction::MulLoop { ref offsets } => {
+                folded.push(Instruction::MulLoop { offsets: offsets.clone() });
+                folded_positions.push(source_positions[i]);
+                i += 1;
+            },
+        }
+    }
+
+    (folded, folded_positions)
+}
+
+/// A loop body is a "clear loop" (`[-]` or `[+]`) if it's exactly one `Add` of `1` or `-1`: such
+/// a loop always terminates with the current cell at zero.
+fn is_clear_loop(body: &[Instruction]) -> bool {
+    match body {
+        [Instruction::Add(1)] | [Instruction::Add(-1)] => true,
+        _ => false,
+    }
+}
+
+/// A loop body is a "multiply/copy loop" if it only moves the pointer and adds to cells, and
+/// returns to its start with a net delta of `-1` on the loop cell. If so, returns the net delta
+/// applied to each other offset visited, for one decrement of the loop cell.
+///
+/// Bails out (returning `None`) if the body contains anything else (I/O, nested loops), the
+/// pointer doesn't return to where it started, the loop cell's own net delta isn't `-1`, or an
+/// offset's accumulated delta doesn't fit in an `i8`.
+fn detect_mul_loop(body: &[Instruction]) -> Option<Vec<(isize, i8)>> {
+    let mut offset = 0isize;
+    let mut deltas: Vec<(isize, i32)> = Vec::new();
+
+    for instruction in body {
+        match *instruction {
+            Instruction::Add(delta) => {
+                match deltas.iter_mut().find(|entry| entry.0 == offset) {
+                    Some(entry) => entry.1 += delta as i32,
+                    None => deltas.push((offset, delta as i32)),
                 }
-                memory[memory_position] = read_buf[0];
+            },
+            Instruction::Move(delta) => offset += delta,
+            // Anything else (I/O, a nested loop) disqualifies this as a multiply/copy loop.
+            _ => return None,
+        }
+    }
+
+    // The pointer must return to where the loop started.
+    if offset != 0 {
+        return None;
+    }
+    // The loop cell itself must be decremented by exactly one per iteration, or the loop
+    // wouldn't behave like a bounded multiply.
+    match deltas.iter().find(|entry| entry.0 == 0) {
+        Some(&(_, -1)) => {},
+        _ => return None,
+    }
+
+    let mut offsets = Vec::with_capacity(deltas.len() - 1);
+    for (offset, delta) in deltas {
+        if offset == 0 {
+            continue;
+        }
+        if delta < i8::MIN as i32 || delta > i8::MAX as i32 {
+            return None;
+        }
+        offsets.push((offset, delta as i8));
+    }
+
+    Some(offsets)
+}
+
+/// A reusable brainfuck interpreter that can be driven one I/O event at a time, instead of
+/// blocking on `io::Read`/`io::Write` like `execute_brainfuck` does.
+///
+/// This is useful for embedding a brainfuck program in an event loop: feed it input as it
+/// becomes available (e.g. keystrokes), rather than needing it all up-front.
+pub struct Interpreter {
+    // Program memory, sized by `config.tape_size`
+    memory: Vec<u8>,
+    // Current position in memory
+    memory_position: usize,
+    // Next instruction to run
+    next_instruction: usize,
+    // The program being run
+    instructions: Vec<Instruction>,
+    // Bytes queued up for `,` to consume
+    input_buffer: VecDeque<u8>,
+    // Instructions left to execute before reporting MaximumIterationsReached, if any limit was
+    // set with `with_max_iterations`.
+    iterations_remaining: Option<u64>,
+    // Cell/pointer/EOF semantics to use while executing
+    config: ExecutionConfig,
+    // Source offset each entry in `instructions` was parsed from, if set with
+    // `with_source_positions`. Always the same length as `instructions` when present.
+    source_positions: Option<Vec<usize>>,
+}
+
+impl Interpreter {
+    /// Create a new interpreter for the given program, starting at the first instruction with
+    /// blank memory, using `ExecutionConfig::default()`.
+    pub fn new(instructions: Vec<Instruction>) -> Interpreter {
+        Interpreter::with_config(instructions, ExecutionConfig::default())
+    }
+
+    /// Create a new interpreter for the given program, using a custom `ExecutionConfig`.
+    pub fn with_config(instructions: Vec<Instruction>, config: ExecutionConfig) -> Interpreter {
+        Interpreter {
+            memory: vec![0u8; config.tape_size],
+            memory_position: 0usize,
+            next_instruction: 0usize,
+            instructions: instructions,
+            input_buffer: VecDeque::new(),
+            iterations_remaining: None,
+            config: config,
+            source_positions: None,
+        }
+    }
+
+    /// Stop after running `maximum_iterations` more instructions, reporting
+    /// `IoEvent::Halted(ExecutionTerminationCondition::MaximumIterationsReached)` if that budget
+    /// runs out before the program does.
+    pub fn with_max_iterations(mut self, maximum_iterations: u64) -> Interpreter {
+        self.iterations_remaining = Some(maximum_iterations);
+        self
+    }
+
+    /// Attach source offsets (from `parse_instructions_with_positions`, or carried through
+    /// `optimize_instructions_with_positions`) so that runtime errors can report the source
+    /// position of the instruction that caused them.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `source_positions` isn't the same length as the instruction vector this
+    /// `Interpreter` was built with. If the instructions were optimized, make sure the positions
+    /// went through `optimize_instructions_with_positions` alongside them rather than being taken
+    /// from the pre-optimized `ParsedProgram`.
+    pub fn with_source_positions(mut self, source_positions: Vec<usize>) -> Interpreter {
+        assert_eq!(source_positions.len(), self.instructions.len(),
+            "source_positions must be the same length as the instructions given to the Interpreter");
+        self.source_positions = Some(source_positions);
+        self
+    }
+
+    /// Queue bytes for a future `,` to read, appending them after any input already queued.
+    pub fn add_input(&mut self, bytes: &[u8]) {
+        self.input_buffer.extend(bytes);
+    }
+
+    /// Resolve a `,` that's waiting on input by reporting that the input stream has reached its
+    /// end, applying `config.eof_behavior` to the current cell and moving past the `,`. Execution
+    /// continues on the next call to `advance_until_io`.
+    ///
+    /// Only call this right after `advance_until_io` returns `IoEvent::NeedInput`.
+    pub fn signal_eof(&mut self) {
+        match self.config.eof_behavior {
+            EofBehavior::Unchanged => {},
+            EofBehavior::Zero => self.memory[self.memory_position] = 0,
+            EofBehavior::Max => self.memory[self.memory_position] = 255,
+        }
+        self.next_instruction += 1;
+    }
+
+    /// Move the pointer by `delta`, applying `config.pointer_overflow` and returning the
+    /// resulting position without moving there.
+    fn resolve_offset(&self, delta: isize) -> Result<usize, Error> {
+        let shifted = self.memory_position as isize + delta;
+        match self.config.pointer_overflow {
+            PointerOverflow::Wrap => Ok(shifted.rem_euclid(self.memory.len() as isize) as usize),
+            PointerOverflow::Error => {
+                if shifted < 0 || shifted >= self.memory.len() as isize {
+                    Err(Error::PointerOutOfBounds)
+                } else {
+                    Ok(shifted as usize)
+                }
+            },
+        }
+    }
+
+    /// Build an `IoEvent::Halted` reporting `kind`, attaching the source position of the current
+    /// instruction (if known) and a snapshot of the tape around the memory pointer.
+    fn runtime_error(&self, kind: Error) -> IoEvent {
+        let source_pos = self.source_positions.as_ref()
+            .and_then(|positions| positions.get(self.next_instruction))
+            .map(|&pos| pos);
+
+        let start = self.memory_position.saturating_sub(RUNTIME_ERROR_SNAPSHOT_RADIUS);
+        let end = (self.memory_position + RUNTIME_ERROR_SNAPSHOT_RADIUS + 1).min(self.memory.len());
+
+        IoEvent::Halted(ExecutionTerminationCondition::Error(RuntimeError {
+            kind: kind,
+            source_pos: source_pos,
+            memory: self.memory[start..end].to_vec(),
+            pointer: self.memory_position - start,
+        }))
+    }
+
+    /// Move the memory pointer by `delta`, applying `config.pointer_overflow`.
+    fn move_pointer(&mut self, delta: isize) -> Result<(), Error> {
+        self.memory_position = try!(self.resolve_offset(delta));
+        Ok(())
+    }
+
+    /// Add `delta` to the cell at `position`, applying `config.cell_overflow`.
+    fn add_at(&mut self, position: usize, delta: i32) -> Result<(), Error> {
+        let result = self.memory[position] as i32 + delta;
+        match self.config.cell_overflow {
+            CellOverflow::Wrap => {
+                self.memory[position] = result as u8;
+                Ok(())
+            },
+            CellOverflow::Error => {
+                if result < 0 || result > 255 {
+                    Err(Error::CellOverflow)
+                } else {
+                    self.memory[position] = result as u8;
+                    Ok(())
+                }
+            },
+        }
+    }
+
+    /// Run instructions until the program needs to output a byte, needs input it doesn't have,
+    /// or halts, then return control to the caller instead of blocking.
+    ///
+    /// Calling this again after `IoEvent::NeedInput` retries the same `,` instruction, so be
+    /// sure to `add_input` (or `signal_eof`) first or it'll just return `NeedInput` again.
+    pub fn advance_until_io(&mut self) -> IoEvent {
+        loop {
+            if self.next_instruction >= self.instructions.len() {
+                // We've reached the end of the instructions
+                return IoEvent::Halted(ExecutionTerminationCondition::AllInstructionsFinished);
+            }
+            // A `,` with nothing queued doesn't actually execute (see the `Instruction::Input`
+            // arm below, which leaves `next_instruction` unchanged so it's retried later), so it
+            // shouldn't cost an iteration either. Otherwise a caller polling `advance_until_io`
+            // once per event-loop tick while waiting on the next keystroke would burn its whole
+            // iteration budget on polls that made zero program progress.
+            let needs_input = match self.instructions[self.next_instruction] {
+                Instruction::Input => self.input_buffer.is_empty(),
+                _ => false,
+            };
+            if needs_input {
+                return IoEvent::NeedInput;
+            }
+            if let Some(remaining) = self.iterations_remaining {
+                if remaining == 0 {
+                    return IoEvent::Halted(ExecutionTerminationCondition::MaximumIterationsReached);
+                }
+                self.iterations_remaining = Some(remaining - 1);
+            }
+
+            match self.instructions[self.next_instruction] {
+                Instruction::MoveRight => {
+                    if let Err(err) = self.move_pointer(1) {
+                        return self.runtime_error(err);
+                    }
+                },
+                Instruction::MoveLeft => {
+                    if let Err(err) = self.move_pointer(-1) {
+                        return self.runtime_error(err);
+                    }
+                },
+                Instruction::Increment => {
+                    let position = self.memory_position;
+                    if let Err(err) = self.add_at(position, 1) {
+                        return self.runtime_error(err);
+                    }
+                },
+                Instruction::Decrement => {
+                    let position = self.memory_position;
+                    if let Err(err) = self.add_at(position, -1) {
+                        return self.runtime_error(err);
+                    }
+                },
+                Instruction::Output => {
+                    let byte = self.memory[self.memory_position];
+                    self.next_instruction += 1;
+                    return IoEvent::Output(byte);
+                },
+                Instruction::Input => {
+                    // The `needs_input` check above already returned `IoEvent::NeedInput`
+                    // (leaving `next_instruction` pointing at this `,` to retry once more input
+                    // is queued or `signal_eof` is called) if the buffer was empty, so it can't
+                    // be empty here.
+                    let byte = self.input_buffer.pop_front()
+                        .expect("needs_input guard above guarantees input_buffer is non-empty");
+                    self.memory[self.memory_position] = byte;
+                },
+                // Apply a coalesced run of Increment/Decrement in one step.
+                Instruction::Add(delta) => {
+                    let position = self.memory_position;
+                    if let Err(err) = self.add_at(position, delta as i32) {
+                        return self.runtime_error(err);
+                    }
+                },
+                // Apply a coalesced run of MoveRight/MoveLeft in one step.
+                Instruction::Move(delta) => {
+                    if let Err(err) = self.move_pointer(delta) {
+                        return self.runtime_error(err);
+                    }
+                },
+                // A folded clear loop (`[-]`/`[+]`): set the current cell to zero in one step.
+                Instruction::SetZero => self.memory[self.memory_position] = 0,
+                // A folded multiply/copy loop: if the current cell is nonzero, spread it across
+                // the recorded offsets scaled by their factors, then zero it.
+                Instruction::MulLoop { ref offsets } => {
+                    // Cloned so the loop below can borrow `self` mutably to apply each offset.
+                    let offsets = offsets.clone();
+                    let factor = self.memory[self.memory_position] as i32;
+                    if factor != 0 {
+                        for &(offset, multiplier) in offsets.iter() {
+                            let target = match self.resolve_offset(offset) {
+                                Ok(position) => position,
+                                Err(err) => {
+                                    return self.runtime_error(err);
+                                },
+                            };
+                            if let Err(err) = self.add_at(target, factor * multiplier as i32) {
+                                return self.runtime_error(err);
+                            }
+                        }
+                        self.memory[self.memory_position] = 0;
+                    }
+                },
+                Instruction::JumpToLeft(target_position) => {
+                    if self.memory[self.memory_position] == 0 {
+                        self.next_instruction = target_position;
+                        continue; // this avoids the automatic incrementing of next_instruction below.
+                    }
+                },
+                Instruction::JumpToRight(target_position) => {
+                    if self.memory[self.memory_position] != 0 {
+                        self.next_instruction = target_position;
+                        continue; // this avoids the automatic incrementing of next_instruction below.
+                    }
+                },
             }
-            Instruction::JumpToLeft(target_position) => {
-                if memory[memory_position as usize] == 0 {
-                    next_instruction = target_position;
-                    continue; // this avoids the automatic incrementing of next_instruction below.
+            self.next_instruction += 1;
+        }
+    }
+}
+
+pub fn execute_brainfuck<O, I>(instructions: Vec<Instruction>, output: O, input: I,
+        maximum_iterations: u64) -> io::Result<ExecutionTerminationCondition>
+        where O: io::Write, I: io::Read {
+    execute_brainfuck_with_config(instructions, output, input, maximum_iterations,
+        ExecutionConfig::default())
+}
+
+/// Like `execute_brainfuck`, but with custom cell/pointer/EOF semantics. See `ExecutionConfig`.
+pub fn execute_brainfuck_with_config<O, I>(instructions: Vec<Instruction>, mut output: O, mut input: I,
+        maximum_iterations: u64, config: ExecutionConfig) -> io::Result<ExecutionTerminationCondition>
+        where O: io::Write, I: io::Read {
+
+    let mut interpreter = Interpreter::with_config(instructions, config)
+        .with_max_iterations(maximum_iterations);
+    // Buffer used for reading input
+    let mut read_buf = [0u8; 1];
+
+    loop {
+        match interpreter.advance_until_io() {
+            IoEvent::Output(byte) => try!(write!(&mut output, "{}", &(byte as char))),
+            IoEvent::NeedInput => {
+                if try!(input.read(&mut read_buf)) >= 1 {
+                    interpreter.add_input(&read_buf);
+                } else {
+                    // End of the input stream: resolve `,` per `config.eof_behavior` instead of
+                    // retrying forever.
+                    interpreter.signal_eof();
                 }
             },
-            Instruction::JumpToRight(target_position) => {
-                if memory[memory_position as usize] != 0 {
-                    next_instruction = target_position;
-                    continue; // this avoids the automatic incrementing of next_instruction below.
+            IoEvent::Halted(condition) => return Ok(condition),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error_config() -> ExecutionConfig {
+        ExecutionConfig {
+            cell_overflow: CellOverflow::Error,
+            pointer_overflow: PointerOverflow::Error,
+            .. ExecutionConfig::default()
+        }
+    }
+
+    #[test]
+    fn coalesce_runs_retargets_jumps() {
+        // `[>+<]` isn't a clear/multiply loop, so it survives coalescing and folding intact;
+        // this just exercises that the `[`/`]` still point at each other after the runs around
+        // it are coalesced into `Add`s.
+        let instructions = parse_instructions("++[>+<]++".chars()).unwrap();
+        let optimized = optimize_instructions(instructions, &ExecutionConfig::default());
+
+        match optimized[1] {
+            Instruction::JumpToLeft(target) => assert_eq!(target, 5),
+            ref other => panic!("expected JumpToLeft(5), found {:?}", other),
+        }
+        match optimized[5] {
+            Instruction::JumpToRight(target) => assert_eq!(target, 1),
+            ref other => panic!("expected JumpToRight(1), found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn fold_loops_retargets_jumps_around_unfoldable_loop() {
+        // `[>+<]` isn't a clear loop or a multiply loop (net delta on the loop cell is 0, not
+        // -1), so it must survive folding with correctly retargeted jumps.
+        let instructions = parse_instructions("+[>+<]".chars()).unwrap();
+        let optimized = optimize_instructions(instructions, &ExecutionConfig::default());
+
+        let left_jump_position = optimized.iter()
+            .position(|instruction| match *instruction { Instruction::JumpToLeft(_) => true, _ => false })
+            .expect("expected an unfolded JumpToLeft to remain");
+        match optimized[left_jump_position] {
+            Instruction::JumpToLeft(target) => {
+                match optimized[target] {
+                    Instruction::JumpToRight(back_target) => assert_eq!(back_target, left_jump_position),
+                    ref other => panic!("expected JumpToRight, found {:?}", other),
                 }
             },
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn detect_mul_loop_accumulates_deltas_at_each_offset() {
+        // `[->+>+<<]`: move right twice adding one each time, net -1 on the loop cell.
+        let body = vec![
+            Instruction::Add(-1),
+            Instruction::Move(1),
+            Instruction::Add(1),
+            Instruction::Move(1),
+            Instruction::Add(1),
+            Instruction::Move(-2),
+        ];
+        let offsets = detect_mul_loop(&body).expect("should be recognized as a multiply loop");
+        assert_eq!(offsets, vec![(1, 1), (2, 1)]);
+    }
+
+    #[test]
+    fn detect_mul_loop_merges_repeat_visits_to_the_same_offset() {
+        // `[->++>+<+<]`-ish body: offset 1 is visited twice, deltas should accumulate.
+        let body = vec![
+            Instruction::Add(-1),
+            Instruction::Move(1),
+            Instruction::Add(2),
+            Instruction::Move(1),
+            Instruction::Add(1),
+            Instruction::Move(-1),
+            Instruction::Add(1),
+            Instruction::Move(-1),
+        ];
+        let offsets = detect_mul_loop(&body).expect("should be recognized as a multiply loop");
+        assert_eq!(offsets, vec![(1, 3), (2, 1)]);
+    }
+
+    #[test]
+    fn detect_mul_loop_rejects_loop_that_does_not_return_pointer() {
+        let body = vec![Instruction::Add(-1), Instruction::Move(1), Instruction::Add(1)];
+        assert_eq!(detect_mul_loop(&body), None);
+    }
+
+    #[test]
+    fn detect_mul_loop_rejects_loop_with_io() {
+        let body = vec![Instruction::Add(-1), Instruction::Output];
+        assert_eq!(detect_mul_loop(&body), None);
+    }
+
+    #[test]
+    fn optimize_instructions_skips_folding_under_error_config() {
+        // `>><<<>>>` nets to a single `Move(2)` if coalesced, which would erase the
+        // `PointerOutOfBounds` the unfolded program legitimately hits moving left off cell 0.
+        let instructions = parse_instructions(">><<<>>>".chars()).unwrap();
+        let config = error_config();
+        let optimized = optimize_instructions(instructions, &config);
+
+        let result = execute_brainfuck_with_config(optimized, io::sink(), io::empty(), 1000, config).unwrap();
+        match result {
+            ExecutionTerminationCondition::Error(RuntimeError { kind: Error::PointerOutOfBounds, .. }) => {},
+            other => panic!("expected PointerOutOfBounds, found {:?}", other),
         }
-        next_instruction += 1;
     }
 
-    // We reached the maximum iteration count
-    return Ok(ExecutionTerminationCondition::MaximumIterationsReached);
+    #[test]
+    fn optimize_instructions_with_positions_stays_aligned() {
+        let program = parse_instructions_with_positions("++++[-]".chars()).unwrap();
+        let config = ExecutionConfig::default();
+        let optimized = optimize_instructions_with_positions(program, &config);
+
+        assert_eq!(optimized.instructions.len(), optimized.source_positions.len());
+        // Both `Add(4)` and `SetZero` should be attributed to the first character that
+        // contributed to them.
+        assert_eq!(optimized.source_positions, vec![0, 4]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn with_source_positions_panics_on_length_mismatch() {
+        let instructions = parse_instructions("++++[-]".chars()).unwrap();
+        Interpreter::new(instructions).with_source_positions(vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn runtime_error_reports_source_pos_memory_and_pointer() {
+        // 256 `+`s (one too many) at source offset 256, preceded by a `>` that puts the pointer
+        // at cell 1 so the snapshot window's reported pointer isn't trivially 0.
+        let source = format!(">{}", "+".repeat(256));
+        let program = parse_instructions_with_positions(source.chars()).unwrap();
+        let config = error_config();
+        let optimized = optimize_instructions_with_positions(program, &config);
+
+        let mut interpreter = Interpreter::with_config(optimized.instructions, config)
+            .with_source_positions(optimized.source_positions)
+            .with_max_iterations(10000);
+
+        let halted = loop {
+            match interpreter.advance_until_io() {
+                IoEvent::Halted(condition) => break condition,
+                other => panic!("expected Halted, found {:?}", other),
+            }
+        };
+
+        match halted {
+            ExecutionTerminationCondition::Error(error) => {
+                assert!(matches!(error.kind, Error::CellOverflow));
+                // The offending `+` is the 256th one, at source offset 256 (`>` took offset 0).
+                assert_eq!(error.source_pos, Some(256));
+                // The pointer sits at cell 1, and is still within RUNTIME_ERROR_SNAPSHOT_RADIUS
+                // of the start of the tape, so the snapshot starts at cell 0.
+                assert_eq!(error.pointer, 1);
+                assert_eq!(error.memory[error.pointer], 255);
+            },
+            other => panic!("expected CellOverflow, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn cell_overflow_error_trips_on_real_overflow() {
+        let instructions = parse_instructions("+".repeat(256).chars()).unwrap();
+        let result = execute_brainfuck_with_config(instructions, io::sink(), io::empty(), 1000, error_config()).unwrap();
+        match result {
+            ExecutionTerminationCondition::Error(RuntimeError { kind: Error::CellOverflow, .. }) => {},
+            other => panic!("expected CellOverflow, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn eof_behavior_zero_sets_the_cell_to_zero_on_end_of_input() {
+        let config = ExecutionConfig { eof_behavior: EofBehavior::Zero, .. ExecutionConfig::default() };
+        let instructions = parse_instructions("+,.".chars()).unwrap();
+        let mut buffer = Vec::new();
+        let result = execute_brainfuck_with_config(instructions, &mut buffer, io::empty(), 1000, config).unwrap();
+
+        match result {
+            ExecutionTerminationCondition::AllInstructionsFinished => {},
+            other => panic!("expected AllInstructionsFinished, found {:?}", other),
+        }
+        // `+` set the cell to 1, then `,` hit EOF and (per `EofBehavior::Zero`) reset it to 0,
+        // so `.` should have output a NUL byte rather than the original `1`.
+        assert_eq!(&buffer[..], &[0u8]);
+    }
+
+    #[test]
+    fn custom_tape_size_is_honored() {
+        let config = ExecutionConfig { tape_size: 4, pointer_overflow: PointerOverflow::Error, .. ExecutionConfig::default() };
+        // `>` three times lands exactly on the last cell (index 3) of a 4-cell tape; a fourth
+        // `>` moves past the end.
+        let instructions = parse_instructions(">>>>".chars()).unwrap();
+        let result = execute_brainfuck_with_config(instructions, io::sink(), io::empty(), 1000, config).unwrap();
+
+        match result {
+            ExecutionTerminationCondition::Error(RuntimeError { kind: Error::PointerOutOfBounds, .. }) => {},
+            other => panic!("expected PointerOutOfBounds, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn advance_until_io_does_not_charge_iterations_for_need_input() {
+        // `,.`: with no input queued, polling should report NeedInput for free, over and over,
+        // without ever spending the iteration budget needed to actually run the `,` and `.`.
+        let instructions = parse_instructions(",.".chars()).unwrap();
+        let mut interpreter = Interpreter::new(instructions).with_max_iterations(2);
+
+        for _ in 0..5 {
+            match interpreter.advance_until_io() {
+                IoEvent::NeedInput => {},
+                other => panic!("expected NeedInput, found {:?}", other),
+            }
+        }
+
+        interpreter.add_input(b"A");
+        match interpreter.advance_until_io() {
+            IoEvent::Output(byte) => assert_eq!(byte, b'A'),
+            other => panic!("expected Output(b'A'), found {:?}", other),
+        }
+        match interpreter.advance_until_io() {
+            IoEvent::Halted(ExecutionTerminationCondition::AllInstructionsFinished) => {},
+            other => panic!("expected AllInstructionsFinished, found {:?}", other),
+        }
+    }
 }